@@ -0,0 +1,135 @@
+use crate::error::{ParseError, ParseErrorKind};
+use crate::message::Message;
+
+/// Maximum length of a single IRC line if no other limit is configured, per
+/// RFC 1459/2812 (512 bytes including the trailing CR LF).
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 512;
+
+/// Incrementally decodes `Message`s out of a byte stream.
+///
+/// Bytes arrive off a socket in arbitrary chunks, so `feed` accumulates them
+/// into an internal buffer and `next_message` pulls complete lines back out
+/// one at a time, parsing each into a `Message` and leaving any trailing
+/// partial line buffered for the next call.
+#[derive(Debug)]
+pub struct MessageDecoder {
+    buf: Vec<u8>,
+    max_line_length: usize,
+}
+
+impl Default for MessageDecoder {
+    fn default() -> Self {
+        MessageDecoder::new()
+    }
+}
+
+impl MessageDecoder {
+    pub fn new() -> Self {
+        MessageDecoder::with_max_line_length(DEFAULT_MAX_LINE_LENGTH)
+    }
+
+    pub fn with_max_line_length(max_line_length: usize) -> Self {
+        MessageDecoder {
+            buf: Vec::new(),
+            max_line_length,
+        }
+    }
+
+    /// Appends freshly read bytes to the internal buffer.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Pulls the next complete, `\n`-terminated line out of the buffer and
+    /// parses it into a `Message`.
+    ///
+    /// Returns `Ok(None)` if the buffer doesn't yet contain a full line.
+    /// Returns an error if the line (including its terminator) would exceed
+    /// the configured max line length, or if it fails to parse. Either way,
+    /// the oversized/unparseable bytes are drained from the buffer before
+    /// returning, so the caller can keep decoding the bytes that follow
+    /// rather than getting the same error forever.
+    pub fn next_message(&mut self) -> Result<Option<Message>, ParseError> {
+        let newline_pos = match self.buf.iter().position(|&b| b == b'\n') {
+            Some(pos) => pos,
+            None => {
+                if self.buf.len() > self.max_line_length {
+                    let len = self.buf.len();
+                    // No terminator yet, so we have no idea where this line
+                    // would have ended; the whole buffer is unrecoverable
+                    // garbage, so drop it all and start fresh.
+                    self.buf.clear();
+                    return Err(ParseError::new(
+                        ParseErrorKind::LineTooLong(len, self.max_line_length),
+                        0,
+                        "",
+                    ));
+                }
+                return Ok(None);
+            }
+        };
+
+        if newline_pos + 1 > self.max_line_length {
+            let len = newline_pos + 1;
+            self.buf.drain(..=newline_pos);
+            return Err(ParseError::new(
+                ParseErrorKind::LineTooLong(len, self.max_line_length),
+                0,
+                "",
+            ));
+        }
+
+        let line: Vec<u8> = self.buf.drain(..=newline_pos).collect();
+
+        // Trim the trailing \n and an optional preceding \r.
+        let mut end = line.len() - 1;
+        if end > 0 && line[end - 1] == b'\r' {
+            end -= 1;
+        }
+
+        let text = std::str::from_utf8(&line[..end])?;
+        text.parse::<Message>().map(Some)
+    }
+}
+
+#[cfg(feature = "tokio-codec")]
+mod codec {
+    use bytes::BytesMut;
+    use tokio_util::codec::Decoder;
+
+    use super::MessageDecoder;
+    use crate::error::{ParseError, ParseErrorKind};
+    use crate::message::Message;
+
+    impl Decoder for MessageDecoder {
+        type Item = Message;
+        type Error = ParseError;
+
+        fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+            if !src.is_empty() {
+                self.feed(&src[..]);
+                src.clear();
+            }
+
+            self.next_message()
+        }
+
+        fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+            if let Some(msg) = self.decode(src)? {
+                return Ok(Some(msg));
+            }
+
+            // `decode` always funnels `src` into our own `buf` and clears it,
+            // so `tokio_util`'s default `decode_eof` (which checks `src` for
+            // leftover bytes) would never see a truncated final line. Check
+            // `buf` ourselves instead.
+            if !self.buf.is_empty() {
+                let len = self.buf.len();
+                self.buf.clear();
+                return Err(ParseError::new(ParseErrorKind::TruncatedLine(len), 0, ""));
+            }
+
+            Ok(None)
+        }
+    }
+}
@@ -1,16 +1,82 @@
 use thiserror::Error as ThisError;
 
-#[derive(Debug, ThisError)]
-pub enum ParseError {
-    #[error("error parsing tags: {0}")]
-    TagError(nom::Err<nom::error::ErrorKind>),
+/// What kind of failure a [`ParseError`] reports: the part of a message that
+/// failed to parse, or a line-level problem (too long, truncated, not UTF-8).
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+pub enum ParseErrorKind {
+    #[error("error parsing tags")]
+    Tags,
 
-    #[error("error parsing prefix: {0}")]
-    PrefixError(nom::Err<nom::error::ErrorKind>),
+    #[error("error parsing prefix")]
+    Prefix,
 
-    #[error("error parsing tags: {0}")]
-    CommandError(nom::Err<nom::error::ErrorKind>),
+    #[error("error parsing command")]
+    Command,
 
-    #[error("error parsing params: {0}")]
-    ParamsError(nom::Err<nom::error::ErrorKind>),
+    #[error("error parsing params")]
+    Params,
+
+    #[error("line too long ({0} bytes, max {1})")]
+    LineTooLong(usize, usize),
+
+    #[error("stream ended with {0} unterminated bytes buffered")]
+    TruncatedLine(usize),
+
+    #[error("invalid utf-8")]
+    Utf8,
+}
+
+/// An error encountered while parsing a [`crate::Message`].
+///
+/// Unlike a bare error string, this carries the byte offset into the
+/// original input where parsing broke down, plus the offending span, so
+/// callers can point a user (or a log line) at exactly what was wrong.
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+#[error("{kind} at byte {offset} (near {span:?})")]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub offset: usize,
+    pub span: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(kind: ParseErrorKind, offset: usize, span: impl Into<String>) -> Self {
+        ParseError {
+            kind,
+            offset,
+            span: span.into(),
+        }
+    }
+}
+
+impl From<std::str::Utf8Error> for ParseError {
+    fn from(err: std::str::Utf8Error) -> Self {
+        ParseError::new(ParseErrorKind::Utf8, err.valid_up_to(), "")
+    }
+}
+
+/// A non-fatal issue noticed by [`crate::Message::parse_lenient`].
+///
+/// Lenient parsing keeps going after one of these instead of aborting, so a
+/// bot can log protocol violations from a misbehaving server without
+/// dropping the whole line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseWarning {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl ParseWarning {
+    pub(crate) fn new(offset: usize, message: impl Into<String>) -> Self {
+        ParseWarning {
+            offset,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
 }
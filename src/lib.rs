@@ -0,0 +1,11 @@
+mod decoder;
+mod error;
+mod escaped;
+mod message;
+mod parser;
+#[cfg(feature = "serde")]
+mod serde_impl;
+
+pub use decoder::MessageDecoder;
+pub use error::{ParseError, ParseErrorKind, ParseWarning};
+pub use message::{Command, Message, Prefix, TagKey};
@@ -4,20 +4,20 @@ use std::fmt::Write;
 use std::option::Option;
 use std::str::FromStr;
 
-use super::error::Error;
-
-use crate::escaped::{escape_char, unescape_char};
+use crate::error::{ParseError, ParseWarning};
+use crate::escaped::escape_char;
+use crate::parser;
 
 #[derive(Debug, PartialEq, Default)]
 pub struct Message {
     pub tags: BTreeMap<String, String>,
-    pub prefix: Option<String>,
-    pub command: String,
+    pub prefix: Option<Prefix>,
+    pub command: Command,
     pub params: Vec<String>,
 }
 
 impl Message {
-    pub fn new(command: String, params: Vec<String>) -> Self {
+    pub fn new(command: Command, params: Vec<String>) -> Self {
         Message {
             command,
             params,
@@ -27,8 +27,8 @@ impl Message {
 
     pub fn new_with_all(
         tags: BTreeMap<String, String>,
-        prefix: Option<String>,
-        command: String,
+        prefix: Option<Prefix>,
+        command: Command,
         params: Vec<String>,
     ) -> Self {
         Message {
@@ -39,7 +39,7 @@ impl Message {
         }
     }
 
-    pub fn new_with_prefix(command: String, params: Vec<String>, prefix: String) -> Self {
+    pub fn new_with_prefix(command: Command, params: Vec<String>, prefix: Prefix) -> Self {
         Message {
             prefix: Some(prefix),
             command,
@@ -47,119 +47,184 @@ impl Message {
             ..Default::default()
         }
     }
+
+    /// Parses `input`, recording non-fatal issues as [`ParseWarning`]s
+    /// instead of aborting on the first one.
+    ///
+    /// This lets a bot log protocol violations from a misbehaving server
+    /// (an empty tag key, a missing command, a stray separator) while still
+    /// getting back a best-effort `Message`.
+    pub fn parse_lenient(input: &str) -> (Message, Vec<ParseWarning>) {
+        parser::parse_message_lenient(input)
+    }
+
+    /// Returns the tags keyed by their structured [`TagKey`] form (client
+    /// prefix and vendor namespace split out) instead of the raw key
+    /// string.
+    pub fn parsed_tags(&self) -> impl Iterator<Item = (TagKey, &str)> {
+        self.tags
+            .iter()
+            .map(|(k, v)| (k.parse().expect("TagKey parsing is infallible"), v.as_str()))
+    }
 }
 
-fn parse_tags(input: &str) -> Result<BTreeMap<String, String>, Error> {
-    let mut tags = BTreeMap::new();
-
-    for tag_data in input.split(';') {
-        let mut pieces = tag_data.splitn(2, '=');
-        let tag_name = pieces
-            .next()
-            .ok_or_else(|| Error::TagError("missing tag name".to_string()))?;
-        let raw_tag_value = pieces.next().unwrap_or("");
-
-        let mut tag_value = String::new();
-        let mut tag_value_chars = raw_tag_value.chars();
-        while let Some(c) = tag_value_chars.next() {
-            if c == '\\' {
-                if let Some(escaped_char) = tag_value_chars.next() {
-                    tag_value.push(unescape_char(escaped_char));
-                }
-            } else {
-                tag_value.push(c);
-            }
-        }
+/// A command verb, either a named command like `PRIVMSG` or a three-digit
+/// numeric reply like `001`.
+///
+/// Numerics are kept as a `u16` rather than a `String` so reply codes can be
+/// matched and compared without reparsing; `Display` always renders them
+/// zero-padded back to three digits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Named(String),
+    Numeric(u16),
+}
 
-        tags.insert(tag_name.to_string(), tag_value);
+impl Command {
+    /// Returns the numeric reply code, if this is a `Numeric` command.
+    pub fn as_numeric(&self) -> Option<u16> {
+        match self {
+            Command::Numeric(n) => Some(*n),
+            Command::Named(_) => None,
+        }
     }
+}
 
-    Ok(tags)
+impl Default for Command {
+    fn default() -> Self {
+        Command::Named(String::new())
+    }
 }
 
-impl FromStr for Message {
-    type Err = Error;
+impl FromStr for Command {
+    type Err = ParseError;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        // We want a mutable input so we can jump through it as we parse the
-        // message. Note that this shadows the input param on purpose so it
-        // cannot accidentally be used later.
-        let mut input = input;
-
-        // Possibly chop off the ending \r\n where either of those characters is
-        // optional.
-        if input.ends_with('\n') {
-            input = &input[..input.len() - 1];
+        if input.len() == 3 && input.bytes().all(|b| b.is_ascii_digit()) {
+            // Guaranteed to fit a u16 and to parse, since we just checked
+            // it's exactly three ASCII digits.
+            Ok(Command::Numeric(input.parse().unwrap()))
+        } else {
+            Ok(Command::Named(input.to_ascii_uppercase()))
         }
-        if input.ends_with('\r') {
-            input = &input[..input.len() - 1];
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Named(name) => f.write_str(name),
+            Command::Numeric(n) => write!(f, "{:03}", n),
         }
+    }
+}
 
-        let mut tags = BTreeMap::new();
-        let mut prefix = None;
+/// The source of a message, as found after the leading `:` in `:nick!user@host
+/// COMMAND ...`.
+///
+/// Follows the grammar `nick [ [ "!" user ] "@" host ]`: a server prefix is
+/// just a hostname and parses as `nick`-only, while a client prefix may carry
+/// a user and/or host.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Prefix {
+    pub nick: String,
+    pub user: Option<String>,
+    pub host: Option<String>,
+}
 
-        if input.starts_with('@') {
-            let mut parts = (&input[1..]).splitn(2, ' ');
-            let tag_data = parts
-                .next()
-                .ok_or_else(|| Error::TagError("failed to parse tag data".to_string()))?;
+impl FromStr for Prefix {
+    type Err = ParseError;
 
-            tags = parse_tags(tag_data)?;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (rest, host) = match input.find('@') {
+            Some(idx) => (&input[..idx], Some(input[idx + 1..].to_string())),
+            None => (input, None),
+        };
+
+        let (nick, user) = match rest.find('!') {
+            Some(idx) => (rest[..idx].to_string(), Some(rest[idx + 1..].to_string())),
+            None => (rest.to_string(), None),
+        };
+
+        Ok(Prefix { nick, user, host })
+    }
+}
+
+impl fmt::Display for Prefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.nick)?;
 
-            // Either advance to the next token, or return an empty string.
-            input = parts.next().unwrap_or("").trim_start_matches(' ');
+        if let Some(user) = &self.user {
+            f.write_char('!')?;
+            f.write_str(user)?;
         }
 
-        if input.starts_with(':') {
-            let mut parts = (&input[1..]).splitn(2, ' ');
-            prefix = Some(
-                parts
-                    .next()
-                    .ok_or_else(|| Error::TagError("failed to parse tag data".to_string()))?
-                    .to_string(),
-            );
-
-            // Either advance to the next token, or return an empty string.
-            input = parts.next().unwrap_or("").trim_start_matches(' ');
+        if let Some(host) = &self.host {
+            f.write_char('@')?;
+            f.write_str(host)?;
         }
 
-        let mut parts = input.splitn(2, ' ');
-        let command = parts
-            .next()
-            .ok_or_else(|| Error::CommandError("missing command".to_string()))?
-            .to_string();
-
-        // Either advance to the next token, or return an empty string.
-        input = parts.next().unwrap_or("").trim_start_matches(' ');
-
-        // Parse out the params
-        let mut params = Vec::new();
-        while !input.is_empty() {
-            // Special case - if the param starts with a :, it's a trailing
-            // param, so we need to include the rest of the input as the param.
-            if input.starts_with(':') {
-                params.push(input[1..].to_string());
-                break;
-            }
+        Ok(())
+    }
+}
 
-            let mut parts = input.splitn(2, ' ');
-            if let Some(param) = parts.next() {
-                params.push(param.to_string());
-            }
+/// A structured view of an IRCv3 message tag key, as in `@+vendor/key=value`.
+///
+/// A leading `+` marks a client-only tag, and a `vendor/key` form carries a
+/// vendor namespace for the tag separately from its local name. Plain keys
+/// have neither.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagKey {
+    pub client_prefix: bool,
+    pub vendor: Option<String>,
+    pub key: String,
+}
 
-            // Either advance to the next token, or return an empty string.
-            input = parts.next().unwrap_or("").trim_start_matches(' ');
-        }
+impl FromStr for TagKey {
+    type Err = ParseError;
 
-        Ok(Message {
-            tags,
-            prefix,
-            command,
-            params,
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (client_prefix, rest) = match input.strip_prefix('+') {
+            Some(rest) => (true, rest),
+            None => (false, input),
+        };
+
+        let (vendor, key) = match rest.find('/') {
+            Some(idx) => (Some(rest[..idx].to_string()), rest[idx + 1..].to_string()),
+            None => (None, rest.to_string()),
+        };
+
+        Ok(TagKey {
+            client_prefix,
+            vendor,
+            key,
         })
     }
 }
 
+impl fmt::Display for TagKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.client_prefix {
+            f.write_char('+')?;
+        }
+
+        if let Some(vendor) = &self.vendor {
+            f.write_str(vendor)?;
+            f.write_char('/')?;
+        }
+
+        f.write_str(&self.key)
+    }
+}
+
+impl FromStr for Message {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        parser::parse_message(input)
+    }
+}
+
 impl fmt::Display for Message {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if !self.tags.is_empty() {
@@ -192,11 +257,11 @@ impl fmt::Display for Message {
 
         if let Some(prefix) = &self.prefix {
             f.write_char(':')?;
-            f.write_str(prefix)?;
+            write!(f, "{}", prefix)?;
             f.write_char(' ')?;
         }
 
-        f.write_str(&self.command)?;
+        write!(f, "{}", self.command)?;
 
         if let Some((last, params)) = self.params.split_last() {
             for param in params {
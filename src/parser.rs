@@ -0,0 +1,308 @@
+//! The grammar for splitting a raw line into the sections (`tags`, `prefix`,
+//! `command`, `params`) that make up a [`Message`].
+//!
+//! Each section is built from small, composable nom combinators; a thin
+//! driving loop sits on top where the grammar is inherently variable-length
+//! (the tag list, the params list), the same way `nom::multi::many0` is
+//! itself just such a loop around a combinator. Every parser reports
+//! failures as a [`ParseError`] that carries the byte offset into the
+//! original line, plus a short span of the offending text, so callers can
+//! point at exactly where a malformed line broke down.
+
+use std::collections::BTreeMap;
+
+use nom::bytes::complete::{take_till, take_till1};
+use nom::character::complete::{char, space0};
+use nom::combinator::opt;
+use nom::error::Error as NomError;
+use nom::multi::separated_list0;
+use nom::IResult;
+
+use crate::error::{ParseError, ParseErrorKind, ParseWarning};
+use crate::escaped::unescape_char;
+use crate::message::{Command, Message, Prefix};
+
+type NomResult<'a, O> = IResult<&'a str, O, NomError<&'a str>>;
+
+/// Byte offset of `current` into `original`, assuming `current` is a
+/// subslice of `original` (which nom parsers preserve).
+fn offset_of(original: &str, current: &str) -> usize {
+    current.as_ptr() as usize - original.as_ptr() as usize
+}
+
+fn span_near(input: &str) -> String {
+    input.chars().take(16).collect()
+}
+
+fn to_parse_error(kind: ParseErrorKind, original: &str, err: nom::Err<NomError<&str>>) -> ParseError {
+    let failing_input = match &err {
+        nom::Err::Error(e) | nom::Err::Failure(e) => e.input,
+        nom::Err::Incomplete(_) => original,
+    };
+
+    ParseError::new(kind, offset_of(original, failing_input), span_near(failing_input))
+}
+
+fn spaces(input: &str) -> NomResult<&str> {
+    space0(input)
+}
+
+fn strip_line_ending(input: &str) -> &str {
+    let input = input.strip_suffix('\n').unwrap_or(input);
+    input.strip_suffix('\r').unwrap_or(input)
+}
+
+/// Tags, prefix, and command tokens must all be non-empty: `@ PRIVMSG`,
+/// `: PRIVMSG`, and a blank/whitespace-only line are malformed, not empty
+/// sections, so these use `take_till1` rather than `take_till` to make that
+/// a real (and located) nom failure instead of a silently-empty match.
+fn tags_section(input: &str) -> NomResult<&str> {
+    let (input, _) = char('@')(input)?;
+    let (input, tag_data) = take_till1(|c| c == ' ')(input)?;
+    let (input, _) = space0(input)?;
+    Ok((input, tag_data))
+}
+
+fn prefix_section(input: &str) -> NomResult<&str> {
+    let (input, _) = char(':')(input)?;
+    let (input, prefix_data) = take_till1(|c| c == ' ')(input)?;
+    let (input, _) = space0(input)?;
+    Ok((input, prefix_data))
+}
+
+/// The command token itself, without consuming the separator that follows
+/// it (callers that need to measure the separator, e.g. lenient parsing,
+/// use this instead of [`command_section`]). Fails on a blank/whitespace-only
+/// remainder, since a line needs a command.
+fn command_token(input: &str) -> NomResult<&str> {
+    take_till1(|c| c == ' ')(input)
+}
+
+fn command_section(input: &str) -> NomResult<&str> {
+    let (input, command_data) = command_token(input)?;
+    let (input, _) = space0(input)?;
+    Ok((input, command_data))
+}
+
+/// A single `key` or `key=value` tag, stopping before the next `;` (if any).
+fn tag_pair(input: &str) -> NomResult<(&str, &str)> {
+    let (input, key) = take_till(|c| c == '=' || c == ';')(input)?;
+    let (input, eq) = opt(char('='))(input)?;
+    let (input, value) = if eq.is_some() {
+        take_till(|c| c == ';')(input)?
+    } else {
+        (input, "")
+    };
+
+    Ok((input, (key, value)))
+}
+
+/// The full `;`-separated tag list, as raw `(key, value)` slices.
+fn tag_pairs(input: &str) -> NomResult<Vec<(&str, &str)>> {
+    separated_list0(char(';'), tag_pair)(input)
+}
+
+/// A non-trailing param: everything up to the next space (or end of input),
+/// with the separating space(s) consumed.
+fn middle_param(input: &str) -> NomResult<&str> {
+    let (input, param) = take_till(|c| c == ' ')(input)?;
+    let (input, _) = space0(input)?;
+    Ok((input, param))
+}
+
+/// The trailing `:`-prefixed param, which runs to the end of the input.
+fn trailing_param(input: &str) -> NomResult<&str> {
+    let (input, _) = char(':')(input)?;
+    Ok(("", input))
+}
+
+fn unescape_value(raw: &str) -> String {
+    let mut value = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(escaped) = chars.next() {
+                value.push(unescape_char(escaped));
+            }
+            // A lone trailing backslash has nothing to escape; drop it.
+        } else {
+            value.push(c);
+        }
+    }
+
+    value
+}
+
+fn parse_tags(original: &str, tag_data: &str) -> Result<BTreeMap<String, String>, ParseError> {
+    let (_, pairs) =
+        tag_pairs(tag_data).map_err(|e| to_parse_error(ParseErrorKind::Tags, original, e))?;
+
+    let mut tags = BTreeMap::new();
+    for (key, raw_value) in pairs {
+        if key.is_empty() {
+            return Err(ParseError::new(
+                ParseErrorKind::Tags,
+                offset_of(original, key),
+                span_near(key),
+            ));
+        }
+
+        tags.insert(key.to_string(), unescape_value(raw_value));
+    }
+
+    Ok(tags)
+}
+
+fn parse_tags_lenient(
+    original: &str,
+    tag_data: &str,
+    warnings: &mut Vec<ParseWarning>,
+) -> BTreeMap<String, String> {
+    let mut tags = BTreeMap::new();
+
+    let pairs = match tag_pairs(tag_data) {
+        Ok((_, pairs)) => pairs,
+        Err(_) => {
+            warnings.push(ParseWarning::new(offset_of(original, tag_data), "malformed tag data"));
+            return tags;
+        }
+    };
+
+    for (key, raw_value) in pairs {
+        if key.is_empty() {
+            warnings.push(ParseWarning::new(offset_of(original, key), "empty tag key"));
+        } else {
+            tags.insert(key.to_string(), unescape_value(raw_value));
+        }
+    }
+
+    tags
+}
+
+fn parse_params(mut input: &str) -> Vec<String> {
+    let mut params = Vec::new();
+
+    while !input.is_empty() {
+        if let Ok((rest, trailing)) = trailing_param(input) {
+            params.push(trailing.to_string());
+            input = rest;
+            break;
+        }
+
+        let (rest, param) = middle_param(input).expect("take_till never fails");
+        params.push(param.to_string());
+        input = rest;
+    }
+
+    params
+}
+
+/// Parses `input` into a [`Message`], failing on the first malformed
+/// section.
+pub(crate) fn parse_message(original: &str) -> Result<Message, ParseError> {
+    let input = strip_line_ending(original);
+
+    let mut tags = BTreeMap::new();
+    let mut remaining = input;
+
+    if remaining.starts_with('@') {
+        let (rest, tag_data) =
+            tags_section(remaining).map_err(|e| to_parse_error(ParseErrorKind::Tags, original, e))?;
+        tags = parse_tags(original, tag_data)?;
+        remaining = rest;
+    }
+
+    let mut prefix = None;
+    if remaining.starts_with(':') {
+        let (rest, prefix_data) =
+            prefix_section(remaining).map_err(|e| to_parse_error(ParseErrorKind::Prefix, original, e))?;
+        prefix = Some(prefix_data.parse::<Prefix>()?);
+        remaining = rest;
+    }
+
+    let (rest, command_data) =
+        command_section(remaining).map_err(|e| to_parse_error(ParseErrorKind::Command, original, e))?;
+    let command = command_data.parse::<Command>()?;
+    remaining = rest;
+
+    let params = parse_params(remaining);
+
+    Ok(Message {
+        tags,
+        prefix,
+        command,
+        params,
+    })
+}
+
+/// Parses `input` into a best-effort [`Message`], recording non-fatal
+/// problems as [`ParseWarning`]s instead of aborting on the first one.
+pub(crate) fn parse_message_lenient(original: &str) -> (Message, Vec<ParseWarning>) {
+    let input = strip_line_ending(original);
+    let mut warnings = Vec::new();
+
+    let mut tags = BTreeMap::new();
+    let mut remaining = input;
+
+    if remaining.starts_with('@') {
+        match tags_section(remaining) {
+            Ok((rest, tag_data)) => {
+                tags = parse_tags_lenient(original, tag_data, &mut warnings);
+                remaining = rest;
+            }
+            Err(_) => {
+                warnings.push(ParseWarning::new(offset_of(original, remaining), "malformed tag data"));
+            }
+        }
+    }
+
+    let mut prefix = None;
+    if remaining.starts_with(':') {
+        match prefix_section(remaining) {
+            Ok((rest, prefix_data)) => {
+                prefix = prefix_data.parse::<Prefix>().ok();
+                remaining = rest;
+            }
+            Err(_) => {
+                warnings.push(ParseWarning::new(offset_of(original, remaining), "malformed prefix"));
+            }
+        }
+    }
+
+    let command = match command_token(remaining) {
+        Ok((rest, command_data)) => {
+            let command = command_data.parse::<Command>().unwrap_or_default();
+
+            // A single space is the separator; anything beyond that is a
+            // stray space worth flagging rather than silently absorbing.
+            let (rest_trimmed, _) = spaces(rest).expect("space0 never fails");
+            let spaces_consumed = rest.len() - rest_trimmed.len();
+            if spaces_consumed > 1 {
+                warnings.push(ParseWarning::new(
+                    offset_of(original, rest),
+                    "stray space before trailing param",
+                ));
+            }
+
+            remaining = rest_trimmed;
+            command
+        }
+        Err(_) => {
+            warnings.push(ParseWarning::new(offset_of(original, remaining), "missing command"));
+            Command::default()
+        }
+    };
+
+    let params = parse_params(remaining);
+
+    (
+        Message {
+            tags,
+            prefix,
+            command,
+            params,
+        },
+        warnings,
+    )
+}
@@ -0,0 +1,81 @@
+//! `serde` support for [`Message`] and [`Prefix`], gated behind the `serde`
+//! feature.
+//!
+//! `Message` serializes to the natural `{ tags, source, verb, params }`
+//! shape (rather than mirroring its internal field names) so YAML/JSON test
+//! vectors and config files can deserialize straight into it.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use serde::de::Error as DeError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::message::{Command, Message, Prefix};
+
+#[derive(Serialize, Deserialize)]
+struct MessageShadow {
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+    source: Option<String>,
+    verb: String,
+    #[serde(default)]
+    params: Vec<String>,
+}
+
+impl Serialize for Message {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        MessageShadow {
+            tags: self.tags.clone(),
+            source: self.prefix.as_ref().map(Prefix::to_string),
+            verb: self.command.to_string(),
+            params: self.params.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Message {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let shadow = MessageShadow::deserialize(deserializer)?;
+
+        let prefix = shadow
+            .source
+            .map(|s| Prefix::from_str(&s))
+            .transpose()
+            .map_err(DeError::custom)?;
+        let command = Command::from_str(&shadow.verb).map_err(DeError::custom)?;
+
+        Ok(Message {
+            tags: shadow.tags,
+            prefix,
+            command,
+            params: shadow.params,
+        })
+    }
+}
+
+impl Serialize for Prefix {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Prefix {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = String::deserialize(deserializer)?;
+        Prefix::from_str(&raw).map_err(DeError::custom)
+    }
+}
@@ -2,36 +2,35 @@ use std::collections::BTreeMap;
 
 use serde::{Deserialize, Serialize};
 
-use simple_irc::{Message, Prefix};
-
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
-struct TestAtoms {
-    #[serde(default)]
-    tags: BTreeMap<String, String>,
-    source: Option<String>,
-    verb: String,
-    #[serde(default)]
-    params: Vec<String>,
-}
-
+use simple_irc::{Message, MessageDecoder, Prefix, TagKey};
+
+// `Message`'s own (de)serialized shape is `{ tags, source, verb, params }` —
+// exactly what the msg-split/msg-join fixtures already look like — so these
+// deserialize straight into `Message` rather than a bespoke mirror struct.
+// That requires the `serde` feature, which this crate's dev-dependency on
+// itself enables for tests.
+#[cfg(feature = "serde")]
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 struct MsgSplitTest {
     input: String,
-    atoms: TestAtoms,
+    atoms: Message,
 }
 
+#[cfg(feature = "serde")]
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 struct MsgSplitTests {
     tests: Vec<MsgSplitTest>,
 }
 
+#[cfg(feature = "serde")]
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 struct MsgJoinTest {
     desc: String,
     matches: Vec<String>,
-    atoms: TestAtoms,
+    atoms: Message,
 }
 
+#[cfg(feature = "serde")]
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 struct MsgJoinTests {
     tests: Vec<MsgJoinTest>,
@@ -55,6 +54,7 @@ struct UserhostSplitTests {
     tests: Vec<UserhostSplitTest>,
 }
 
+#[cfg(feature = "serde")]
 #[test]
 fn test_msg_split() {
     let msg_split_test_data = include_str!("external/parser-tests/tests/msg-split.yaml");
@@ -75,69 +75,22 @@ fn test_msg_split() {
 
         let msg = res.unwrap();
 
-        let mut msg_tags = msg.tags.clone();
-
-        // Loop through all the test tags and make sure they were there.
-        for (key, value) in test.atoms.tags {
-            assert_eq!(
-                value,
-                msg_tags.remove(key.as_str()).unwrap(),
-                "Mismatched value for key {}",
-                key.as_str()
-            );
-
-            // Remove any keys we found from msg_tags so we can ensure there
-            // were no leftovers later.
-            msg_tags.remove(key.as_str());
-        }
-
-        // If there are any tags left over in msg_tags, this is an error.
-        for (key, value) in msg_tags {
-            assert!(false, "Extra value {} for key {}", value, key);
-        }
-
         assert_eq!(
-            test.atoms.source,
-            msg.prefix.as_ref().map(|p| p.to_string()),
-            "msg prefix mismatch: expected \"{:?}\" got \"{:?}\"",
-            test.atoms.source,
-            msg.prefix.as_ref().map(|p| p.to_string()),
-        );
-
-        assert_eq!(
-            test.atoms.verb, msg.command,
-            "msg command mismatch: expected \"{}\" got \"{}\"",
-            test.atoms.verb, msg.command,
-        );
-
-        assert_eq!(
-            test.atoms.params, msg.params,
-            "msg params mismatch: expected \"{:?}\" got \"{:?}\"",
-            test.atoms.params, msg.params,
+            test.atoms, msg,
+            "msg mismatch for \"{}\": expected {:?}, got {:?}",
+            &test.input, test.atoms, msg,
         );
     }
 }
 
+#[cfg(feature = "serde")]
 #[test]
 fn test_msg_join() {
     let msg_split_test_data = include_str!("external/parser-tests/tests/msg-join.yaml");
     let tests = serde_yaml::from_str::<MsgJoinTests>(msg_split_test_data).unwrap();
 
     for test in tests.tests {
-        let mut tags = BTreeMap::new();
-
-        for (k, v) in test.atoms.tags.iter() {
-            tags.insert(k.clone(), v.clone());
-        }
-
-        let msg = Message {
-            tags,
-            prefix: test.atoms.source.map(|s| s.parse().unwrap()),
-            command: test.atoms.verb,
-            params: test.atoms.params,
-        };
-
-        let out = format!("{}", msg);
+        let out = format!("{}", test.atoms);
 
         assert!(
             test.matches.contains(&out.to_string()),
@@ -176,3 +129,154 @@ fn test_userhost_split() {
         assert_eq!(prefix.to_string(), test.source);
     }
 }
+
+#[test]
+fn test_decoder_feeds_across_chunks() {
+    let mut decoder = MessageDecoder::new();
+
+    decoder.feed(b"PRIVMSG #chan");
+    assert_eq!(decoder.next_message().unwrap(), None);
+
+    decoder.feed(b" :hi\r\n");
+    let msg = decoder.next_message().unwrap().unwrap();
+    assert_eq!(msg.command, "PRIVMSG".parse().unwrap());
+    assert_eq!(msg.params, vec!["#chan".to_string(), "hi".to_string()]);
+
+    assert_eq!(decoder.next_message().unwrap(), None);
+}
+
+#[test]
+fn test_decoder_handles_lf_only() {
+    let mut decoder = MessageDecoder::new();
+
+    decoder.feed(b"PING :PONG\n");
+    let msg = decoder.next_message().unwrap().unwrap();
+    assert_eq!(msg.command, "PING".parse().unwrap());
+    assert_eq!(msg.params, vec!["PONG".to_string()]);
+}
+
+#[test]
+fn test_decoder_recovers_after_oversized_line() {
+    let mut decoder = MessageDecoder::with_max_line_length(16);
+
+    let long_line = format!("PRIVMSG #chan :{}\n", "x".repeat(40));
+    decoder.feed(long_line.as_bytes());
+    let err = decoder.next_message().unwrap_err();
+    // `offset` is "position in the original input" by contract; a too-long
+    // line doesn't have a meaningful one of those, so it must not be
+    // fabricated from the configured limit.
+    assert_eq!(err.offset, 0);
+
+    // The oversized line must be drained, not left wedged at the front of
+    // the buffer, so a subsequent valid line still decodes.
+    decoder.feed(b"PING :PONG\r\n");
+    let msg = decoder.next_message().unwrap().unwrap();
+    assert_eq!(msg.command, "PING".parse().unwrap());
+}
+
+#[test]
+fn test_decoder_recovers_after_oversized_line_with_no_terminator_yet() {
+    let mut decoder = MessageDecoder::with_max_line_length(16);
+
+    decoder.feed(&b"x".repeat(40));
+    let err = decoder.next_message().unwrap_err();
+    assert_eq!(err.offset, 0);
+
+    decoder.feed(b"PING :PONG\r\n");
+    let msg = decoder.next_message().unwrap().unwrap();
+    assert_eq!(msg.command, "PING".parse().unwrap());
+}
+
+#[cfg(feature = "tokio-codec")]
+#[test]
+fn test_decoder_tokio_codec_impl() {
+    use bytes::BytesMut;
+    use tokio_util::codec::Decoder as _;
+
+    let mut decoder = MessageDecoder::new();
+    let mut buf = BytesMut::from(&b"PING :PONG\r\n"[..]);
+
+    let msg = decoder.decode(&mut buf).unwrap().unwrap();
+    assert_eq!(msg.command, "PING".parse().unwrap());
+}
+
+#[cfg(feature = "tokio-codec")]
+#[test]
+fn test_decoder_tokio_codec_decode_eof_surfaces_truncated_line() {
+    use bytes::BytesMut;
+    use tokio_util::codec::Decoder as _;
+
+    let mut decoder = MessageDecoder::new();
+    let mut buf = BytesMut::from(&b"PRIVMSG #chan :bye"[..]);
+
+    // A connection that closes mid-line (no trailing \n) must not silently
+    // drop the buffered partial line.
+    assert!(decoder.decode_eof(&mut buf).is_err());
+}
+
+#[cfg(feature = "tokio-codec")]
+#[test]
+fn test_decoder_tokio_codec_decode_eof_passes_through_clean_close() {
+    use bytes::BytesMut;
+    use tokio_util::codec::Decoder as _;
+
+    let mut decoder = MessageDecoder::new();
+    let mut buf = BytesMut::new();
+
+    assert_eq!(decoder.decode_eof(&mut buf).unwrap(), None);
+}
+
+#[test]
+fn test_tag_value_trailing_backslash_is_dropped() {
+    let msg: Message = "@foo=bar\\ :nick PRIVMSG #chan :hi".parse().unwrap();
+
+    assert_eq!(msg.tags.get("foo").unwrap(), "bar");
+}
+
+#[test]
+fn test_tag_value_backslash_round_trip() {
+    let mut tags = BTreeMap::new();
+    tags.insert("foo".to_string(), "a\\b".to_string());
+
+    let msg = Message::new_with_all(tags, None, "PRIVMSG".parse().unwrap(), vec!["hi".to_string()]);
+
+    let out = format!("{}", msg);
+    let reparsed: Message = out.parse().unwrap();
+
+    assert_eq!(reparsed.tags.get("foo").unwrap(), "a\\b");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_message_serde_round_trip() {
+    let json = r#"{"tags":{"foo":"bar"},"source":"nick!user@host","verb":"PRIVMSG","params":["#chan","hi"]}"#;
+
+    let msg: Message = serde_json::from_str(json).unwrap();
+    assert_eq!(msg.tags.get("foo").unwrap(), "bar");
+    assert_eq!(msg.prefix.as_ref().unwrap().nick, "nick");
+    assert_eq!(msg.command, "PRIVMSG".parse().unwrap());
+
+    let out = serde_json::to_string(&msg).unwrap();
+    let reparsed: Message = serde_json::from_str(&out).unwrap();
+    assert_eq!(msg, reparsed);
+}
+
+#[test]
+fn test_tag_key_client_prefix_and_vendor() {
+    let key: TagKey = "+example.com/foo".parse().unwrap();
+
+    assert!(key.client_prefix);
+    assert_eq!(key.vendor.as_deref(), Some("example.com"));
+    assert_eq!(key.key, "foo");
+    assert_eq!(key.to_string(), "+example.com/foo");
+}
+
+#[test]
+fn test_message_from_str_rejects_blank_command() {
+    // A blank/whitespace-only line (e.g. a stray keepalive) has no command
+    // token to parse, so it's a hard error rather than an empty-command
+    // `Message`.
+    assert!("".parse::<Message>().is_err());
+    assert!(" ".parse::<Message>().is_err());
+    assert!("\r\n".parse::<Message>().is_err());
+}